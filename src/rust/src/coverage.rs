@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use extendr_api::prelude::*;
+use geo::{BooleanOps, ChamberlainDuquetteArea};
+use geo_types::{LineString, Polygon};
+use h3o::{CellIndex, LatLng, Resolution};
+
+use crate::createh3::match_resolution;
+use crate::h3::*;
+
+// the boundary of a cell as a polygon of longitude and latitude
+// when `shift` negative longitudes are moved east of the antimeridian
+pub fn cell_polygon(cell: CellIndex, shift: bool) -> Polygon {
+    let coords = cell
+        .boundary()
+        .iter()
+        .map(|ll| {
+            let lng = if shift && ll.lng() < 0.0 {
+                ll.lng() + 360.0
+            } else {
+                ll.lng()
+            };
+            (lng, ll.lat())
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    Polygon::new(LineString::from(coords), vec![])
+}
+
+// does the boundary of the cell cross the antimeridian
+fn crosses_antimeridian(cell: CellIndex) -> bool {
+    let lngs = cell
+        .boundary()
+        .iter()
+        .map(|ll| ll.lng())
+        .collect::<Vec<f64>>();
+    let min = lngs.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = lngs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    max - min > 180.0
+}
+
+// does the cell, or one of its children at `child_res`, contain a pole
+// the longitude and latitude boundaries of those cells do not enclose them
+fn contains_pole(cell: CellIndex, child_res: Resolution) -> bool {
+    [90.0, -90.0].iter().any(|lat| {
+        let pole = LatLng::new(*lat, 0.0).unwrap();
+        pole.to_cell(cell.resolution()) == cell
+            || pole.to_cell(child_res).parent(cell.resolution()) == Some(cell)
+    })
+}
+
+// areas in km^2 of a parent, its children, the part of the parent not covered
+// by the children, and the part of the children outside of the parent
+//
+// children do not overlap so the areas of each child can be summed instead of
+// computing their union
+fn hierarchy_discrepancy(parent: CellIndex, child_res: Resolution) -> [f64; 4] {
+    let shift = crosses_antimeridian(parent);
+    let parent_poly = cell_polygon(parent, shift);
+    let parent_area = parent_poly.chamberlain_duquette_unsigned_area();
+
+    let mut children_area = 0.0;
+    let mut covered = 0.0;
+    let mut outside = 0.0;
+
+    for child in parent.children(child_res) {
+        let child_poly = cell_polygon(child, shift);
+        children_area += child_poly.chamberlain_duquette_unsigned_area();
+        covered += child_poly
+            .intersection(&parent_poly)
+            .chamberlain_duquette_unsigned_area();
+        outside += child_poly
+            .difference(&parent_poly)
+            .chamberlain_duquette_unsigned_area();
+    }
+
+    let uncovered = (parent_area - covered).max(0.0);
+    [parent_area, children_area, uncovered, outside].map(|m2| m2 / 1e6)
+}
+
+#[extendr]
+fn hierarchy_discrepancy_(x: List, child_res: u8) -> List {
+    let reso = match_resolution(child_res);
+
+    let mut parent_area = Vec::with_capacity(x.len());
+    let mut children_area = Vec::with_capacity(x.len());
+    let mut uncovered = Vec::with_capacity(x.len());
+    let mut outside = Vec::with_capacity(x.len());
+
+    for cell in cell_indexes(&x) {
+        // children must be finer than the parent
+        let areas = cell
+            .filter(|cell| cell.resolution() < reso && !contains_pole(*cell, reso))
+            .map(|cell| hierarchy_discrepancy(cell, reso));
+
+        match areas {
+            Some([p, c, u, o]) => {
+                parent_area.push(Rfloat::from(p));
+                children_area.push(Rfloat::from(c));
+                uncovered.push(Rfloat::from(u));
+                outside.push(Rfloat::from(o));
+            }
+            None => {
+                parent_area.push(Rfloat::na());
+                children_area.push(Rfloat::na());
+                uncovered.push(Rfloat::na());
+                outside.push(Rfloat::na());
+            }
+        }
+    }
+
+    list!(
+        parent_area = Doubles::from_values(parent_area),
+        children_area = Doubles::from_values(children_area),
+        uncovered_area = Doubles::from_values(uncovered),
+        outside_area = Doubles::from_values(outside)
+    )
+}
+
+// the position of the cell in `cells` whose boundary contains each point
+//
+// points are assigned using their location rather than the parent of a finer
+// cell. When cells of several resolutions contain a point the finest is used.
+// x is a list of sfg POINTs.
+#[extendr]
+fn assign_points_(x: List, cells: List) -> Integers {
+    let mut lookup: HashMap<CellIndex, usize> = HashMap::new();
+    for (i, cell) in cell_indexes(&cells).into_iter().enumerate() {
+        if let Some(cell) = cell {
+            lookup.entry(cell).or_insert(i);
+        }
+    }
+
+    let mut resolutions = lookup
+        .keys()
+        .map(|cell| cell.resolution())
+        .collect::<Vec<Resolution>>();
+    resolutions.sort();
+    resolutions.dedup();
+    resolutions.reverse();
+
+    x.iter()
+        .map(|(_, pnt)| {
+            let ll = Doubles::try_from(pnt)
+                .ok()
+                .filter(|dbls| dbls.len() == 2 && !dbls[0].is_na() && !dbls[1].is_na())
+                .and_then(|dbls| LatLng::new(dbls[1].inner(), dbls[0].inner()).ok());
+
+            let hit = ll.and_then(|ll| {
+                resolutions
+                    .iter()
+                    .find_map(|res| lookup.get(&ll.to_cell(*res)))
+            });
+
+            match hit {
+                Some(i) => Rint::from(*i as i32 + 1),
+                None => Rint::na(),
+            }
+        })
+        .collect::<Integers>()
+}
+
+extendr_module! {
+    mod coverage;
+    fn hierarchy_discrepancy_;
+    fn assign_points_;
+}
@@ -5,7 +5,36 @@ use extendr_api::Strings;
 use h3o::Resolution;
 use h3o::{CellIndex, LatLng};
 
-use crate::h3::{vctrs_class, H3};
+use crate::h3::{as_h3_vctr, vctrs_class, H3};
+
+// parse a 64-bit index from a string
+// accepts hexadecimal in any case with an optional 0x prefix or a decimal u64
+// surrounding whitespace is ignored
+pub fn parse_index(x: &str) -> Option<u64> {
+    let x = x.trim();
+
+    if let Some(hex) = x.strip_prefix("0x").or_else(|| x.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).ok();
+    }
+
+    let hex = u64::from_str_radix(x, 16).ok();
+
+    // hex IDs can be all digits so decimal is only used if hex is not a valid cell
+    let hex_is_cell = hex.map_or(false, |index| CellIndex::try_from(index).is_ok());
+    if !hex_is_cell && x.chars().all(|c| c.is_ascii_digit()) {
+        if let Ok(dec) = u64::from_str(x) {
+            if hex.is_none() || CellIndex::try_from(dec).is_ok() {
+                return Some(dec);
+            }
+        }
+    }
+
+    hex
+}
+
+fn parse_cell(x: &str) -> Option<CellIndex> {
+    parse_index(x).and_then(|index| CellIndex::try_from(index).ok())
+}
 
 #[extendr]
 fn h3_from_string_(x: Strings) -> List  {
@@ -14,7 +43,10 @@ fn h3_from_string_(x: Strings) -> List  {
             if strng.is_na() {
                 Robj::from(extendr_api::NULL)
             } else {
-                Robj::from(H3::from(CellIndex::from_str(strng.as_str()).unwrap()))
+                match parse_cell(strng.as_str()) {
+                    Some(cell) => Robj::from(H3::from(cell)),
+                    None => Robj::from(extendr_api::NULL),
+                }
             }
         })
         .collect::<Vec<Robj>>();
@@ -100,6 +132,24 @@ fn h3_boundaries_(x: List) -> List {
     List::from_values(res)
 }
 
+#[extendr]
+fn h3_base_cells_() -> List {
+    as_h3_vctr(CellIndex::base_cells())
+}
+
+#[extendr]
+fn h3_pentagons_(resolution: u8) -> List {
+    as_h3_vctr(match_resolution(resolution).pentagons())
+}
+
+// every cell at a resolution in base cell order
+// the number of cells grows by a factor of 7 for each resolution
+#[extendr]
+fn h3_global_grid_(resolution: u8) -> List {
+    let reso = match_resolution(resolution);
+    as_h3_vctr(CellIndex::base_cells().flat_map(move |cell| cell.children(reso)))
+}
+
 pub fn match_resolution(resolution: u8) -> Resolution {
     let tmp = h3o::Resolution::try_from(resolution).unwrap();
     tmp
@@ -111,4 +161,7 @@ extendr_module! {
     fn h3_from_points_;
     fn h3_from_xy_;
     fn h3_boundaries_;
+    fn h3_base_cells_;
+    fn h3_pentagons_;
+    fn h3_global_grid_;
 }
@@ -0,0 +1,228 @@
+use extendr_api::prelude::*;
+use h3o::{CellIndex, LatLng};
+use rayon::prelude::*;
+
+use crate::createh3::match_resolution;
+use crate::h3::*;
+
+// authalic earth radius used by h3o
+const EARTH_RADIUS_KM: f64 = 6371.007180918475;
+
+// great circle distance in the requested unit
+pub fn ll_distance(x: LatLng, y: LatLng, unit: &str) -> f64 {
+    match unit {
+        "m" => x.distance_m(y),
+        "rads" => x.distance_rads(y),
+        _ => x.distance_km(y),
+    }
+}
+
+// the center of each cell in an H3 vector
+fn cell_centers(x: &List) -> Vec<Option<LatLng>> {
+    cell_indexes(x)
+        .into_iter()
+        .map(|cell| cell.map(LatLng::from))
+        .collect::<Vec<Option<LatLng>>>()
+}
+
+#[extendr]
+fn h3_distance_pairwise_(x: List, y: List, unit: &str) -> Doubles {
+    cell_centers(&x)
+        .into_iter()
+        .zip(cell_centers(&y))
+        .map(|(x, y)| match (x, y) {
+            (Some(x), Some(y)) => Rfloat::from(ll_distance(x, y, unit)),
+            _ => Rfloat::na(),
+        })
+        .collect::<Doubles>()
+}
+
+#[extendr]
+fn h3_distance_matrix_(x: List, y: List, unit: &str) -> RMatrix<f64> {
+    let x = cell_centers(&x);
+    let y = cell_centers(&y);
+    let nrow = x.len();
+
+    // filled in column major order to match R
+    let res = (0..(x.len() * y.len()))
+        .into_par_iter()
+        .map(|i| match (x[i % nrow], y[i / nrow]) {
+            (Some(xi), Some(yj)) => ll_distance(xi, yj, unit),
+            _ => Rfloat::na().inner(),
+        })
+        .collect::<Vec<f64>>();
+
+    RMatrix::new_matrix(nrow, y.len(), |r, c| res[c * nrow + r])
+}
+
+// x is an H3 vector, y is a list of sfg POINTs
+#[extendr]
+fn h3_point_distance_(x: List, y: List, unit: &str) -> Doubles {
+    cell_centers(&x)
+        .into_iter()
+        .zip(y.into_iter())
+        .map(|(center, (_, pnt))| {
+            let pnt = Doubles::try_from(pnt)
+                .ok()
+                .filter(|dbls| dbls.len() == 2 && !dbls[0].is_na() && !dbls[1].is_na())
+                .and_then(|dbls| LatLng::new(dbls[1].inner(), dbls[0].inner()).ok());
+
+            match (center, pnt) {
+                (Some(center), Some(pnt)) => Rfloat::from(ll_distance(center, pnt, unit)),
+                _ => Rfloat::na(),
+            }
+        })
+        .collect::<Doubles>()
+}
+
+// unit vector on the sphere
+fn to_xyz(ll: LatLng) -> [f64; 3] {
+    let (lat, lng) = (ll.lat_radians(), ll.lng_radians());
+    [lat.cos() * lng.cos(), lat.cos() * lng.sin(), lat.sin()]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// great circle distance in radians from `p` to the arc between `a` and `b`
+fn arc_distance_rads(p: LatLng, a: LatLng, b: LatLng) -> f64 {
+    let endpoints = p.distance_rads(a).min(p.distance_rads(b));
+    let (pv, av, bv) = (to_xyz(p), to_xyz(a), to_xyz(b));
+
+    let n = cross(av, bv);
+    let norm = dot(n, n).sqrt();
+    if norm == 0.0 {
+        return endpoints;
+    }
+    let n = [n[0] / norm, n[1] / norm, n[2] / norm];
+
+    // the closest point of the great circle lies within the arc
+    let within = dot(cross(av, pv), n) >= 0.0 && dot(cross(pv, bv), n) >= 0.0;
+    if within {
+        dot(pv, n).abs().asin().min(endpoints)
+    } else {
+        endpoints
+    }
+}
+
+// great circle distance in radians from `p` to the boundary of `cell`
+// points inside of the cell have a distance of 0
+fn boundary_distance_rads(p: LatLng, cell: CellIndex) -> f64 {
+    if p.to_cell(cell.resolution()) == cell {
+        return 0.0;
+    }
+
+    let vertexes = cell.boundary().iter().copied().collect::<Vec<LatLng>>();
+    (0..vertexes.len())
+        .map(|i| arc_distance_rads(p, vertexes[i], vertexes[(i + 1) % vertexes.len()]))
+        .fold(f64::INFINITY, f64::min)
+}
+
+// the size of the grid disk that covers `radius_km`
+// k is padded so that smaller than average cells are still covered
+fn disk_k(radius_km: f64, resolution: h3o::Resolution) -> u32 {
+    ((radius_km / resolution.edge_length_km()).ceil() as u32).saturating_add(1)
+}
+
+// the cells within `radius_km` of `p`
+//
+// the radius is converted to a grid disk using the average edge length. When
+// `exact` the
+// cells are filtered by distance to their center or, if `boundary`, the nearest
+// point of their boundary.
+fn cells_within(
+    p: LatLng,
+    radius_km: f64,
+    resolution: h3o::Resolution,
+    exact: bool,
+    boundary: bool,
+) -> Vec<CellIndex> {
+    let origin = p.to_cell(resolution);
+    let k = disk_k(radius_km, resolution);
+    let radius_rads = radius_km / EARTH_RADIUS_KM;
+
+    origin
+        .grid_disk_safe(k)
+        .filter(|cell| {
+            if !exact {
+                true
+            } else if boundary {
+                boundary_distance_rads(p, *cell) <= radius_rads
+            } else {
+                p.distance_rads(LatLng::from(*cell)) <= radius_rads
+            }
+        })
+        .collect::<Vec<CellIndex>>()
+}
+
+#[extendr]
+fn cells_within_(
+    x: Doubles,
+    y: Doubles,
+    radius_km: Doubles,
+    resolution: u8,
+    exact: bool,
+    boundary: bool,
+) -> List {
+    let reso = match_resolution(resolution);
+
+    let res = x
+        .iter()
+        .zip(y.iter())
+        .zip(radius_km.iter())
+        .map(|((x, y), r)| {
+            let ll = if x.is_na() || y.is_na() || r.is_na() {
+                None
+            } else {
+                LatLng::new(y.inner(), x.inner()).ok()
+            };
+
+            let cells = match ll {
+                Some(ll) => cells_within(ll, r.inner(), reso, exact, boundary),
+                None => vec![],
+            };
+
+            List::from_values(cells.into_iter().map(H3::from).collect::<Vec<H3>>())
+                .set_class(vctrs_class())
+                .unwrap()
+                .clone()
+        })
+        .collect::<Vec<List>>();
+
+    List::from_values(res)
+}
+
+// the number of cells in the grid disks searched by cells_within_()
+// a disk of size k has 3k(k + 1) + 1 cells but never more than the whole grid
+#[extendr]
+fn cells_within_size_(radius_km: Doubles, resolution: u8) -> f64 {
+    let reso = match_resolution(resolution);
+    let n_global = 2.0 + 120.0 * 7_f64.powi(resolution as i32);
+
+    radius_km
+        .iter()
+        .filter(|r| !r.is_na())
+        .map(|r| {
+            let k = disk_k(r.inner(), reso) as f64;
+            (3.0 * k * (k + 1.0) + 1.0).min(n_global)
+        })
+        .sum()
+}
+
+extendr_module! {
+    mod distance;
+    fn h3_distance_pairwise_;
+    fn h3_distance_matrix_;
+    fn h3_point_distance_;
+    fn cells_within_;
+    fn cells_within_size_;
+}
@@ -335,7 +335,7 @@ fn edges_to_strings(x: List) -> Strings {
 }
 
 #[extendr]
-fn edge_vctrs() -> [String; 3] {
+pub fn edge_vctrs() -> [String; 3] {
     [
         String::from("H3Edge"),
         String::from("vctrs_vctr"),
@@ -9,7 +9,12 @@ use crate::createh3::match_resolution;
 use crate::h3::*;
 
 use rayon::prelude::*;
-use geo_types::Geometry;
+use geo::{Area, BooleanOps};
+use geo_types::{Geometry, MultiPolygon};
+use h3o::{CellIndex, Resolution};
+use std::collections::HashSet;
+
+use crate::coverage::cell_polygon;
 
 fn geometry_to_cells(x: Geometry, containment: PolyfillConfig) -> Vec<H3> {
     let h3geo = h3o::geom::Geometry::from_degrees(x)
@@ -21,21 +26,25 @@ fn geometry_to_cells(x: Geometry, containment: PolyfillConfig) -> Vec<H3> {
         .collect::<Vec<_>>()
 }
 
-#[extendr]
-fn sfc_to_cells_(x: List, resolution: i32, containment: &str) -> List {
-
-    let resolution = match_resolution(resolution as u8);
-
-    let containment_strategy = match containment {
+// match the `containment` argument to its mode
+// unknown values use ContainsBoundary
+fn containment_mode(containment: &str) -> ContainmentMode {
+    match containment {
         "boundary" => ContainmentMode::ContainsBoundary,
         "centroid" => ContainmentMode::ContainsCentroid,
         "intersect" => ContainmentMode::IntersectsBoundary,
         "covers" => ContainmentMode::Covers,
         _ => ContainmentMode::ContainsBoundary
-    };
+    }
+}
+
+#[extendr]
+fn sfc_to_cells_(x: List, resolution: i32, containment: &str) -> List {
+
+    let resolution = match_resolution(resolution as u8);
 
     let poly_config = PolyfillConfig::new(resolution)
-        .containment_mode(containment_strategy);
+        .containment_mode(containment_mode(containment));
 
     let x = sfc_to_geometry(x);
 
@@ -57,8 +66,118 @@ fn sfc_to_cells_(x: List, resolution: i32, containment: &str) -> List {
     List::from_values(res)
 }
 
+// the fraction of a cell's area outside of the polygons
+fn outside_fraction(cell: CellIndex, polygons: &MultiPolygon) -> f64 {
+    let poly = cell_polygon(cell, false);
+    let area = poly.unsigned_area();
+    if area == 0.0 {
+        return 0.0;
+    }
+
+    let inside = poly.intersection(polygons).unsigned_area();
+    (1.0 - inside / area).max(0.0)
+}
+
+// cover a geometry with cells from `min_res` to `max_res`
+//
+// the cells at `max_res` are found using `config`. Starting at `min_res`
+// the ancestors of those cells are kept if no more than `tolerance` of their
+// area is outside of the geometry. Otherwise they are replaced by their
+// children until `max_res` is reached.
+fn geometry_to_cells_adaptive(
+    x: Geometry,
+    config: PolyfillConfig,
+    min_res: Resolution,
+    max_res: Resolution,
+    tolerance: f64,
+) -> Vec<H3> {
+    let polygons = match &x {
+        Geometry::Polygon(poly) => MultiPolygon::new(vec![poly.clone()]),
+        Geometry::MultiPolygon(mpoly) => mpoly.clone(),
+        _ => return vec![],
+    };
+
+    let fine = match h3o::geom::Geometry::from_degrees(x) {
+        Ok(geom) => geom.to_cells(config).collect::<Vec<CellIndex>>(),
+        Err(_) => return vec![],
+    };
+
+    if min_res > max_res {
+        return vec![];
+    }
+
+    // the ancestors of the fine cells at each resolution
+    let covered = (u8::from(min_res)..=u8::from(max_res))
+        .map(|res| {
+            let res = Resolution::try_from(res).unwrap();
+            fine.iter()
+                .filter_map(|cell| cell.parent(res))
+                .collect::<HashSet<CellIndex>>()
+        })
+        .collect::<Vec<HashSet<CellIndex>>>();
+
+    let mut res = Vec::new();
+    let mut candidates = covered[0].iter().copied().collect::<Vec<CellIndex>>();
+    candidates.sort();
+
+    for level in 0..covered.len() {
+        let mut next = Vec::new();
+
+        for cell in candidates {
+            let is_last = level == covered.len() - 1;
+            if is_last || outside_fraction(cell, &polygons) <= tolerance {
+                res.push(H3::from(cell));
+            } else {
+                let child_res = cell.resolution().succ().unwrap();
+                next.extend(
+                    cell.children(child_res)
+                        .filter(|child| covered[level + 1].contains(child)),
+                );
+            }
+        }
+
+        candidates = next;
+    }
+
+    res
+}
+
+#[extendr]
+fn sfc_to_cells_adaptive_(
+    x: List,
+    max_res: i32,
+    min_res: i32,
+    tolerance: f64,
+    containment: &str,
+) -> List {
+    let max_res = match_resolution(max_res as u8);
+    let min_res = match_resolution(min_res as u8);
+
+    let poly_config = PolyfillConfig::new(max_res)
+        .containment_mode(containment_mode(containment));
+
+    let x = sfc_to_geometry(x);
+
+    let res = x.into_par_iter()
+        .map(|xi| {
+            match xi {
+                Some(xi) => geometry_to_cells_adaptive(xi, poly_config, min_res, max_res, tolerance),
+                None => vec![]
+            }
+        })
+        .collect::<Vec<Vec<H3>>>();
+
+    let res = res.into_iter().map(|xi| {
+        List::from_values(xi).set_class(vctrs_class()).unwrap().clone()
+    })
+    .collect::<Vec<List>>();
+
+    List::from_values(res)
+}
+
 extendr_module! {
     mod fromsf;
     // fn sfg_to_cells;
     fn sfc_to_cells_;
+    fn sfc_to_cells_adaptive_;
 }
@@ -0,0 +1,408 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use extendr_api::prelude::*;
+use h3o::{CellIndex, LatLng};
+use rayon::prelude::*;
+
+use crate::h3::*;
+
+// an entry in the search frontier
+// ordering is reversed so that BinaryHeap pops the cheapest cell first
+#[derive(Debug, Clone, Copy)]
+struct Frontier {
+    priority: f64,
+    cell: CellIndex,
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .total_cmp(&self.priority)
+            .then_with(|| self.cell.cmp(&other.cell))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// the cells sharing an edge with `cell`
+// grid_disk_safe() is used so that pentagons are handled
+fn grid_neighbors(cell: CellIndex) -> impl Iterator<Item = CellIndex> {
+    cell.grid_disk_safe(1).filter(move |nb| *nb != cell)
+}
+
+// the search was stopped because the user interrupted it
+struct Interrupted;
+
+// how many cells are expanded between checks for a user interrupt
+const INTERRUPT_INTERVAL: usize = 10_000;
+
+// A* search from `from` to `to`
+//
+// `costs` maps the traversable cells to the cost of entering them. When it is
+// `None` every cell is traversable at a cost of 1. The heuristic is the grid
+// distance scaled by the cheapest cost which never overestimates. When the
+// grid distance cannot be computed the search falls back to Dijkstra.
+//
+// The search gives up once `max_cells` cells have been expanded.
+fn shortest_path(
+    from: CellIndex,
+    to: CellIndex,
+    costs: Option<&HashMap<CellIndex, f64>>,
+    min_cost: f64,
+    max_cells: usize,
+) -> std::result::Result<Option<(Vec<CellIndex>, f64)>, Interrupted> {
+    if from.resolution() != to.resolution() {
+        return Ok(None);
+    }
+
+    if let Some(costs) = costs {
+        if !costs.contains_key(&from) || !costs.contains_key(&to) {
+            return Ok(None);
+        }
+    }
+
+    let heuristic = |cell: CellIndex| match cell.grid_distance(to) {
+        Ok(d) => d as f64 * min_cost,
+        Err(_) => 0.0,
+    };
+
+    let mut dist: HashMap<CellIndex, f64> = HashMap::new();
+    let mut prev: HashMap<CellIndex, CellIndex> = HashMap::new();
+    let mut frontier = BinaryHeap::new();
+    let mut expanded = 0;
+
+    dist.insert(from, 0.0);
+    frontier.push(Frontier {
+        priority: heuristic(from),
+        cell: from,
+    });
+
+    while let Some(Frontier { priority, cell }) = frontier.pop() {
+        let cell_dist = dist[&cell];
+
+        // a cheaper route to this cell was found after it was queued
+        if priority > cell_dist + heuristic(cell) {
+            continue;
+        }
+
+        if cell == to {
+            let mut path = vec![to];
+            let mut cur = to;
+            while let Some(p) = prev.get(&cur) {
+                path.push(*p);
+                cur = *p;
+            }
+            path.reverse();
+            return Ok(Some((path, cell_dist)));
+        }
+
+        expanded += 1;
+        if expanded > max_cells {
+            return Ok(None);
+        }
+
+        if expanded % INTERRUPT_INTERVAL == 0 && user_interrupted() {
+            return Err(Interrupted);
+        }
+
+        for nb in grid_neighbors(cell) {
+            let step = match costs {
+                Some(costs) => match costs.get(&nb) {
+                    Some(cost) => *cost,
+                    None => continue,
+                },
+                None => 1.0,
+            };
+
+            let candidate = cell_dist + step;
+            let improves = match dist.get(&nb) {
+                Some(d) => candidate < *d,
+                None => true,
+            };
+
+            if improves {
+                dist.insert(nb, candidate);
+                prev.insert(nb, cell);
+                frontier.push(Frontier {
+                    priority: candidate + heuristic(nb),
+                    cell: nb,
+                });
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[extendr]
+fn shortest_path_(from: List, to: List, allowed: List, cost: Doubles, max_cells: f64) -> List {
+    // when no allowed cells are provided all cells can be traversed
+    let costs = if allowed.len() == 0 {
+        None
+    } else {
+        let lookup = cell_indexes(&allowed)
+            .into_iter()
+            .zip(cost.iter())
+            .filter_map(|(cell, cost)| {
+                let cost = if cost.is_na() { 1.0 } else { cost.inner() };
+                cell.map(|cell| (cell, cost))
+            })
+            .collect::<HashMap<CellIndex, f64>>();
+        Some(lookup)
+    };
+
+    let min_cost = match &costs {
+        Some(costs) => costs.values().copied().fold(f64::INFINITY, f64::min),
+        None => 1.0,
+    };
+
+    let pairs = cell_indexes(&from).into_iter().zip(cell_indexes(&to));
+    let mut paths = Vec::new();
+    let mut totals = Vec::new();
+
+    for (x, y) in pairs {
+        let res = match (x, y) {
+            (Some(x), Some(y)) => {
+                match shortest_path(x, y, costs.as_ref(), min_cost, max_cells as usize) {
+                    Ok(res) => res,
+                    Err(Interrupted) => return list!(interrupted = true),
+                }
+            }
+            _ => None,
+        };
+
+        match res {
+            Some((path, total)) => {
+                let path = path.into_iter().map(H3::from).collect::<Vec<H3>>();
+                let path = List::from_values(path)
+                    .set_class(vctrs_class())
+                    .unwrap()
+                    .clone();
+                paths.push(path);
+                totals.push(Rfloat::from(total));
+            }
+            None => {
+                paths.push(list!().set_class(vctrs_class()).unwrap().clone());
+                totals.push(Rfloat::na());
+            }
+        }
+    }
+
+    list!(
+        path = List::from_values(paths),
+        cost = Doubles::from_values(totals)
+    )
+}
+
+// multi-source breadth first search from `seeds` through the cells of `area`
+//
+// returns the nearest seed and its grid distance for each reached cell. Ties
+// are broken in favor of the seed that comes first.
+fn distance_field(
+    area: &HashSet<CellIndex>,
+    seeds: &[Option<CellIndex>],
+    max_k: u32,
+) -> HashMap<CellIndex, (usize, u32)> {
+    let mut field: HashMap<CellIndex, (usize, u32)> = HashMap::new();
+    let mut frontier: Vec<CellIndex> = Vec::new();
+
+    for (i, seed) in seeds.iter().enumerate() {
+        if let Some(seed) = seed {
+            if !field.contains_key(seed) {
+                field.insert(*seed, (i, 0));
+                frontier.push(*seed);
+            }
+        }
+    }
+
+    let mut k = 0;
+    while !frontier.is_empty() && k < max_k {
+        k += 1;
+        let mut next = Vec::new();
+
+        for cell in frontier {
+            let seed = field[&cell].0;
+            for nb in grid_neighbors(cell) {
+                if !area.contains(&nb) {
+                    continue;
+                }
+
+                match field.get_mut(&nb) {
+                    Some(entry) => {
+                        // reached by another seed during this same step
+                        if entry.1 == k && seed < entry.0 {
+                            entry.0 = seed;
+                        }
+                    }
+                    None => {
+                        field.insert(nb, (seed, k));
+                        next.push(nb);
+                    }
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    field
+}
+
+#[extendr]
+fn distance_field_(area: List, seeds: List, max_k: i32) -> List {
+    let area_cells = cell_indexes(&area);
+    let lookup = area_cells
+        .iter()
+        .flatten()
+        .copied()
+        .collect::<HashSet<CellIndex>>();
+
+    let field = distance_field(&lookup, &cell_indexes(&seeds), max_k.max(0) as u32);
+
+    let (seed, distance): (Vec<Rint>, Vec<Rint>) = area_cells
+        .into_iter()
+        .map(|cell| {
+            let hit = cell.and_then(|cell| field.get(&cell));
+            match hit {
+                Some((seed, distance)) => {
+                    (Rint::from(*seed as i32 + 1), Rint::from(*distance as i32))
+                }
+                None => (Rint::na(), Rint::na()),
+            }
+        })
+        .unzip();
+
+    list!(
+        seed = Integers::from_values(seed),
+        distance = Integers::from_values(distance)
+    )
+}
+
+// the nearest candidate to `query` by searching rings of increasing size
+//
+// returns the index of the candidate and its grid distance. When several
+// candidates are in the same ring the one that comes first is used.
+fn nearest_by_ring(
+    query: CellIndex,
+    candidates: &HashMap<CellIndex, usize>,
+    max_k: u32,
+) -> Option<(usize, u32)> {
+    if let Some(i) = candidates.get(&query) {
+        return Some((*i, 0));
+    }
+
+    let mut visited: HashSet<CellIndex> = HashSet::new();
+    visited.insert(query);
+    let mut frontier = vec![query];
+
+    for k in 1..=max_k {
+        let mut next = Vec::new();
+        for cell in frontier {
+            for nb in grid_neighbors(cell) {
+                if visited.insert(nb) {
+                    next.push(nb);
+                }
+            }
+        }
+
+        let hit = next.iter().filter_map(|cell| candidates.get(cell)).min();
+
+        if let Some(i) = hit {
+            return Some((*i, k));
+        }
+
+        frontier = next;
+    }
+
+    None
+}
+
+// the nearest candidate to `query` by the great circle distance between centers
+fn nearest_by_center(query: CellIndex, centers: &[Option<LatLng>]) -> Option<(usize, f64)> {
+    let from = LatLng::from(query);
+    centers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, center)| center.map(|center| (i, from.distance_km(center))))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[extendr]
+fn nearest_(query: List, candidates: List, max_k: i32, fallback: bool) -> List {
+    let candidates = cell_indexes(&candidates);
+    let centers = candidates
+        .iter()
+        .map(|cell| cell.map(LatLng::from))
+        .collect::<Vec<Option<LatLng>>>();
+
+    // keep the first position of duplicated candidates
+    let mut lookup: HashMap<CellIndex, usize> = HashMap::new();
+    for (i, cell) in candidates.iter().enumerate() {
+        if let Some(cell) = cell {
+            lookup.entry(*cell).or_insert(i);
+        }
+    }
+
+    let max_k = max_k.max(0) as u32;
+
+    let res = cell_indexes(&query)
+        .into_par_iter()
+        .map(|cell| {
+            let cell = cell?;
+            match nearest_by_ring(cell, &lookup, max_k) {
+                Some((i, k)) => {
+                    let km = LatLng::from(cell).distance_km(centers[i].unwrap());
+                    Some((i, Some(k), km))
+                }
+                None if fallback => nearest_by_center(cell, &centers).map(|(i, km)| (i, None, km)),
+                None => None,
+            }
+        })
+        .collect::<Vec<Option<(usize, Option<u32>, f64)>>>();
+
+    let mut index = Vec::with_capacity(res.len());
+    let mut distance = Vec::with_capacity(res.len());
+    let mut distance_km = Vec::with_capacity(res.len());
+
+    for hit in res {
+        match hit {
+            Some((i, k, km)) => {
+                index.push(Rint::from(i as i32 + 1));
+                distance.push(k.map_or(Rint::na(), |k| Rint::from(k as i32)));
+                distance_km.push(Rfloat::from(km));
+            }
+            None => {
+                index.push(Rint::na());
+                distance.push(Rint::na());
+                distance_km.push(Rfloat::na());
+            }
+        }
+    }
+
+    list!(
+        index = Integers::from_values(index),
+        distance = Integers::from_values(distance),
+        distance_km = Doubles::from_values(distance_km)
+    )
+}
+
+extendr_module! {
+    mod grid_search;
+    fn shortest_path_;
+    fn distance_field_;
+    fn nearest_;
+}
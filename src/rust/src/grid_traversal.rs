@@ -1,5 +1,7 @@
 use extendr_api::prelude::*;
 use crate::h3::*;
+use h3o::{CellIndex, LatLng};
+use rayon::prelude::*;
 
 #[extendr]
 fn grid_disk_fast_(x: List, k: u32) -> List {
@@ -212,6 +214,48 @@ fn local_ij_(x: List, y: List) -> List {
     list!(i = i, j = j)
 }
 
+#[extendr]
+fn grid_distance_matrix_(x: List, y: List, fallback: bool) -> List {
+    let x = cell_indexes(&x);
+    let y = cell_indexes(&y);
+    let nrow = x.len();
+
+    // filled in column major order to match R
+    // the great circle distance between centers is kept for the pairs whose
+    // grid distance cannot be computed
+    let res = (0..(x.len() * y.len()))
+        .into_par_iter()
+        .map(|i| match (x[i % nrow], y[i / nrow]) {
+            (Some(xi), Some(yj)) => match xi.grid_distance(yj) {
+                Ok(d) => (d, f64::NAN),
+                Err(_) if fallback => {
+                    let km = LatLng::from(xi).distance_km(LatLng::from(yj));
+                    (i32::MIN, km)
+                }
+                Err(_) => (i32::MIN, f64::NAN),
+            },
+            _ => (i32::MIN, f64::NAN),
+        })
+        .collect::<Vec<(i32, f64)>>();
+
+    let distance = RMatrix::new_matrix(nrow, y.len(), |r, c| res[c * nrow + r].0);
+
+    if !fallback {
+        return list!(distance = distance, distance_km = extendr_api::NULL);
+    }
+
+    let distance_km = RMatrix::new_matrix(nrow, y.len(), |r, c| {
+        let km = res[c * nrow + r].1;
+        if km.is_nan() {
+            Rfloat::na()
+        } else {
+            Rfloat::from(km)
+        }
+    });
+
+    list!(distance = distance, distance_km = distance_km)
+}
+
 extendr_module! {
     mod grid_traversal;
     fn grid_disk_fast_;
@@ -221,5 +265,6 @@ extendr_module! {
     fn grid_path_cells_;
     fn grid_path_cells_size_;
     fn grid_distance_;
+    fn grid_distance_matrix_;
     fn local_ij_;
 }
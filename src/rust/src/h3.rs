@@ -1,6 +1,8 @@
 use extendr_api::prelude::*;
 
-use h3o::CellIndex;
+use h3o::{CellIndex, Resolution};
+
+use crate::edgefns::H3DEdge;
 
 #[derive(Debug, Clone, Copy)]
 pub struct H3 {
@@ -41,8 +43,90 @@ fn h3_to_strings(x: List) -> Strings {
 
     Strings::from_values(res)
 }
+// split the 64-bit index of H3 cells and edges into its high and low 32 bits
+// doubles are used because they represent every 32 bit unsigned integer
+// comparing the parts lexicographically is the same as comparing the index
+#[extendr]
+fn index_parts_(x: List) -> List {
+    let (hi, lo): (Vec<Rfloat>, Vec<Rfloat>) = x
+        .iter()
+        .map(|(_, robj)| {
+            let index = match <&H3>::try_from(&robj) {
+                Ok(cell) => Some(u64::from(cell.index)),
+                Err(_) => <&H3DEdge>::try_from(&robj)
+                    .ok()
+                    .map(|edge| u64::from(edge.edge)),
+            };
+
+            match index {
+                Some(index) => (
+                    Rfloat::from((index >> 32) as f64),
+                    Rfloat::from((index & 0xffff_ffff) as f64),
+                ),
+                None => (Rfloat::na(), Rfloat::na()),
+            }
+        })
+        .unzip();
+
+    list!(hi = Doubles::from_values(hi), lo = Doubles::from_values(lo))
+}
+
+// extract the cell index of each element of an H3 vector
+// missing or invalid elements are None
+pub fn cell_indexes(x: &List) -> Vec<Option<CellIndex>> {
+    x.iter()
+        .map(|(_, robj)| <&H3>::try_from(&robj).ok().map(|h3| h3.index))
+        .collect::<Vec<Option<CellIndex>>>()
+}
+
+// create an H3 vector from cells
+// missing cells are NULL
+pub fn as_h3_vctr<T: Into<Option<CellIndex>>>(x: impl IntoIterator<Item = T>) -> List {
+    let res = x
+        .into_iter()
+        .map(|cell| match cell.into() {
+            Some(cell) => Robj::from(H3::from(cell)),
+            None => Robj::from(extendr_api::NULL),
+        })
+        .collect::<Vec<Robj>>();
+
+    List::from_values(res)
+        .set_class(vctrs_class())
+        .unwrap()
+        .clone()
+}
+
+// the parents of a cell from the next coarsest resolution down to 0
+pub fn ancestors(cell: CellIndex) -> impl Iterator<Item = CellIndex> {
+    (0..u8::from(cell.resolution()))
+        .rev()
+        .filter_map(move |res| cell.parent(Resolution::try_from(res).unwrap()))
+}
+
+// whether the user has pressed ctrl-c (or escape) since the last check
+// R_CheckUserInterrupt() is run inside R_ToplevelExec() so that an interrupt
+// returns here instead of jumping over the destructors of the caller
+pub fn user_interrupted() -> bool {
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        fn R_CheckUserInterrupt();
+        fn R_ToplevelExec(
+            fun: Option<unsafe extern "C" fn(*mut c_void)>,
+            data: *mut c_void,
+        ) -> c_int;
+    }
+
+    unsafe extern "C" fn check(_: *mut c_void) {
+        R_CheckUserInterrupt();
+    }
+
+    unsafe { R_ToplevelExec(Some(check), std::ptr::null_mut()) == 0 }
+}
+
 extendr_module! {
     mod h3;
     fn h3_to_strings;
     fn vctrs_class;
+    fn index_parts_;
 }
@@ -1,51 +1,239 @@
-use crate::{createh3::match_resolution, h3::*};
+use std::collections::HashMap;
+
+use crate::createh3::match_resolution;
+use crate::h3::*;
+use crate::sets::{drop_descendants, unique_cells};
 use extendr_api::prelude::*;
-use h3o::CellIndex;
-use std::iter::FromIterator;
+use h3o::{error::CompactionError, CellIndex, Resolution};
+use rayon::prelude::*;
+
+// recycle a vector to `n` elements
+// an empty vector is recycled as None
+fn recycle<T: Copy>(x: &[T], n: usize) -> Vec<Option<T>> {
+    if x.is_empty() {
+        return vec![None; n];
+    }
+
+    (0..n).map(|i| Some(x[i % x.len()])).collect::<Vec<Option<T>>>()
+}
+
+// recycle a resolution vector to `n` elements
+// missing and out of range resolutions are None
+fn recycle_resolutions(resolution: &Integers, n: usize) -> Vec<Option<Resolution>> {
+    recycle(resolution, n)
+        .into_iter()
+        .map(|res| {
+            res.filter(|res| !res.is_na())
+                .and_then(|res| u8::try_from(res.inner()).ok())
+                .and_then(|res| Resolution::try_from(res).ok())
+        })
+        .collect::<Vec<Option<Resolution>>>()
+}
 
 #[extendr]
-fn get_parents_(x: List, resolution: u8) -> List {
-    let reso = match_resolution(resolution);
-    let res = x.into_iter()
-        .map(|(_, robj)| {
-            if robj.is_null() {
-                Robj::from(extendr_api::NULL)
-            } else {
-                let rent = <&H3>::try_from(&robj).unwrap().index.parent(reso);
-                match rent {
-                    Some(rent) => Robj::from(H3::from(rent)),
-                    None => Robj::from(extendr_api::NULL),
-                }
-            }
+fn get_parents_(x: List, resolution: Integers) -> List {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    as_h3_vctr(
+        cells
+            .into_iter()
+            .zip(reso)
+            .map(|(cell, reso)| cell?.parent(reso?)),
+    )
+}
+
+#[extendr]
+fn get_children_(x: List, resolution: Integers) -> List {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    let res = cells
+        .into_iter()
+        .zip(reso)
+        .map(|(cell, reso)| {
+            let children = match (cell, reso) {
+                (Some(cell), Some(reso)) => cell.children(reso).collect::<Vec<CellIndex>>(),
+                _ => vec![],
+            };
+            as_h3_vctr(children)
         })
-        .collect::<Vec<Robj>>();
+        .collect::<Vec<List>>();
 
     List::from_values(res)
-        .set_class(vctrs_class())
-        .unwrap()
-        .clone()
 }
 
 #[extendr]
-fn get_children_(x: List, resolution: u8) -> List {
-    let reso = match_resolution(resolution);
-    let res = x.into_iter()
-        .map(|(_, robj)| {
-            if robj.is_null() {
-                list!()
-                    .set_class(vctrs_class())
-                    .unwrap()
-                    .clone()
+fn get_children_count_(x: List, resolution: Integers) -> Vec<i32> {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    cells
+        .into_iter()
+        .zip(reso)
+        .map(|(cell, reso)| match (cell, reso) {
+            (Some(cell), Some(reso)) => cell.children_count(reso) as i32,
+            _ => i32::MIN,
+        })
+        .collect::<Vec<i32>>()
+}
+
+#[extendr]
+fn get_children_center_(x: List, resolution: Integers) -> List {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    as_h3_vctr(
+        cells
+            .into_iter()
+            .zip(reso)
+            .map(|(cell, reso)| cell?.center_child(reso?)),
+    )
+}
+
+// positions are doubles so that every position up to 7^15 is exact
+#[extendr]
+fn get_children_position_(x: List, resolution: Integers) -> Doubles {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    cells
+        .into_iter()
+        .zip(reso)
+        .map(|(cell, reso)| {
+            let position = match (cell, reso) {
+                (Some(cell), Some(reso)) => cell.child_position(reso),
+                _ => None,
+            };
+
+            match position {
+                Some(position) => Rfloat::from(position as f64),
+                None => Rfloat::na(),
+            }
+        })
+        .collect::<Doubles>()
+}
+
+// the child at each position
+//
+// positions are validated as non-negative whole numbers in R. Cells with a
+// resolution coarser than their own have no children and are missing. The
+// 1-based rows whose position is beyond the last child are returned so that R
+// can raise the error.
+#[extendr]
+fn get_children_at_(x: List, position: Doubles, resolution: Integers) -> List {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+    let position = recycle(&position, cells.len());
+    let mut out_of_range = Vec::new();
+
+    let res = cells
+        .into_iter()
+        .zip(reso)
+        .zip(position)
+        .enumerate()
+        .map(|(i, ((cell, reso), position))| {
+            let position = position.filter(|position| !position.is_na())?;
+            let (cell, reso) = (cell?, reso?);
+
+            if reso < cell.resolution() {
+                return None;
+            }
+
+            let position = position.inner() as u64;
+            if position >= cell.children_count(reso) {
+                out_of_range.push(i as i32 + 1);
+                return None;
+            }
+
+            cell.child_at(position, reso)
+        })
+        .collect::<Vec<Option<CellIndex>>>();
+
+    list!(cell = as_h3_vctr(res), out_of_range = out_of_range)
+}
+
+// compact cells of mixed resolutions
+//
+// duplicates and cells covered by a coarser cell are dropped first. Each
+// resolution is then compacted from finest to coarsest. Parents created at one
+// resolution are compacted again with the cells of their own resolution.
+fn compact_mixed(x: Vec<CellIndex>) -> Result<Vec<CellIndex>, CompactionError> {
+    let mut levels: Vec<Vec<CellIndex>> = vec![Vec::new(); 16];
+    for cell in drop_descendants(unique_cells(x)) {
+        levels[u8::from(cell.resolution()) as usize].push(cell);
+    }
+
+    let mut res = Vec::new();
+    for level in (0..levels.len()).rev() {
+        let cells = std::mem::take(&mut levels[level]);
+        if cells.is_empty() {
+            continue;
+        }
+
+        for cell in CellIndex::compact(cells)? {
+            let cell_level = u8::from(cell.resolution()) as usize;
+            if cell_level == level {
+                res.push(cell);
             } else {
-                let children = <&H3>::try_from(&robj).unwrap().index.children(reso);
-                let r = children
-                    .map(|child| H3::from(child))
-                    .collect::<Vec<H3>>();
-                List::from_values(r)
-                    .set_class(vctrs_class())
-                    .unwrap()
-                    .clone()
+                levels[cell_level].push(cell);
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+// compact the cells of each group
+// `group` contains integer group ids. The result is a long table of the group
+// and compacted cells or a list with an error message.
+#[extendr]
+fn compact_cells_(x: List, group: Integers) -> List {
+    let mut groups: Vec<Vec<CellIndex>> = Vec::new();
+    for (cell, id) in cell_indexes(&x).into_iter().zip(group.iter()) {
+        let cell = match cell {
+            Some(cell) if !id.is_na() && id.inner() > 0 => cell,
+            _ => continue,
+        };
+
+        let id = id.inner() as usize;
+        if groups.len() < id {
+            groups.resize(id, Vec::new());
+        }
+        groups[id - 1].push(cell);
+    }
+
+    let mut ids = Vec::new();
+    let mut cells = Vec::new();
+    for (i, group) in groups.into_iter().enumerate() {
+        match compact_mixed(group) {
+            Ok(compacted) => {
+                ids.extend(std::iter::repeat(i as i32 + 1).take(compacted.len()));
+                cells.extend(compacted);
             }
+            Err(e) => return list!(error = e.to_string()),
+        }
+    }
+
+    list!(group = ids, cell = as_h3_vctr(cells))
+}
+
+#[extendr]
+fn uncompact_cells_(x: List, resolution: Integers) -> List {
+    let cells = cell_indexes(&x);
+    let reso = recycle_resolutions(&resolution, cells.len());
+
+    let res = cells
+        .into_iter()
+        .zip(reso)
+        .map(|(cell, reso)| {
+            let uncompacted = match (cell, reso) {
+                (Some(cell), Some(reso)) => {
+                    CellIndex::uncompact(std::iter::once(cell), reso).collect::<Vec<CellIndex>>()
+                }
+                _ => vec![],
+            };
+            as_h3_vctr(uncompacted)
         })
         .collect::<Vec<List>>();
 
@@ -53,130 +241,259 @@ fn get_children_(x: List, resolution: u8) -> List {
 }
 
 #[extendr]
-fn get_children_count_(x: List, resolution: u8) -> Vec<i32> {
+fn uncompact_size_(x: List, resolution: u8) -> f64 {
     let reso = match_resolution(resolution);
-    x.into_iter()
-        .map(|(_, robj)| {
-            if robj.is_null() {
-                i32::MIN
-            } else {
-                <&H3>::try_from(&robj).unwrap().index.children_count(reso) as i32
-            }
+    CellIndex::uncompact_size(cell_indexes(&x).into_iter().flatten(), reso) as f64
+}
+
+// uncompact the cells of x into a single vector
+// `source` is the position in x of the cell each child came from
+#[extendr]
+fn uncompact_cells_flat_(x: List, resolution: u8, parallel: bool) -> List {
+    let reso = match_resolution(resolution);
+    let cells = cell_indexes(&x)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, cell)| cell.map(|cell| (i, cell)))
+        .collect::<Vec<(usize, CellIndex)>>();
+
+    let children = if parallel {
+        cells
+            .par_iter()
+            .flat_map_iter(|(_, cell)| cell.children(reso))
+            .collect::<Vec<CellIndex>>()
+    } else {
+        CellIndex::uncompact(cells.iter().map(|(_, cell)| *cell), reso).collect::<Vec<CellIndex>>()
+    };
+
+    let source = cells
+        .iter()
+        .flat_map(|(i, cell)| {
+            std::iter::repeat(*i as i32 + 1).take(cell.children_count(reso) as usize)
         })
-        .collect::<Vec<i32>>()
+        .collect::<Vec<i32>>();
+
+    list!(source = source, cell = as_h3_vctr(children))
 }
 
+// the children of each cell at positions `offset` to `offset + n - 1`
+// positions beyond the number of children are skipped
 #[extendr]
-fn get_children_center_(x: List, resolution: u8) -> List {
+fn h3_children_slice_(x: List, resolution: u8, offset: f64, n: f64) -> List {
     let reso = match_resolution(resolution);
-    let res = x.into_iter()
-        .map(|(_, robj)| {
-            if robj.is_null() {
-                Robj::from(extendr_api::NULL)
-            } else {
-                let child = <&H3>::try_from(&robj).unwrap().index.center_child(reso);
-                match child {
-                    Some(child) => Robj::from(H3::from(child)),
-                    None => Robj::from(extendr_api::NULL),
+    let offset = offset.max(0.0) as u64;
+    let n = n.max(0.0) as u64;
+
+    let res = cell_indexes(&x)
+        .into_iter()
+        .map(|cell| {
+            let children = match cell {
+                Some(cell) => {
+                    let end = offset.saturating_add(n).min(cell.children_count(reso));
+                    (offset..end)
+                        .filter_map(|position| cell.child_at(position, reso))
+                        .collect::<Vec<CellIndex>>()
                 }
-            }
+                None => vec![],
+            };
+            as_h3_vctr(children)
         })
-        .collect::<Vec<Robj>>();
+        .collect::<Vec<List>>();
 
     List::from_values(res)
-        .set_class(vctrs_class())
-        .unwrap()
-        .clone()
 }
 
+// the cell and its ancestors from the cell's resolution down to `min_res`
+// as a long table with a row for each pair
 #[extendr]
-fn get_children_position_(x: List, resolution: u8) -> Integers {
-    let reso = match_resolution(resolution);
-    let res = x.into_iter().map(|(_, robj)| {
-        let child = <&H3>::try_from(&robj).unwrap().index.child_position(reso);
+fn h3_ancestors_long_(x: List, min_res: u8) -> List {
+    let mut id = Vec::new();
+    let mut resolution = Vec::new();
+    let mut ancestor = Vec::new();
 
-        match child {
-            Some(child) => Rint::from(child as i32),
-            None => Rint::na(),
+    for (i, cell) in cell_indexes(&x).into_iter().enumerate() {
+        let cell = match cell {
+            Some(cell) => cell,
+            None => continue,
+        };
+
+        for res in (min_res..=u8::from(cell.resolution())).rev() {
+            id.push(i as i32 + 1);
+            resolution.push(res as i32);
+            ancestor.push(cell.parent(Resolution::try_from(res).unwrap()));
         }
-    });
+    }
 
-    Integers::from_iter(res)
+    list!(
+        id = id,
+        resolution = resolution,
+        ancestor = as_h3_vctr(ancestor)
+    )
 }
 
+// an H3 vector of the ancestors of each cell for every resolution from
+// `min_res` to 15. Resolutions finer than the cell are missing.
 #[extendr]
-fn get_children_at_(x: List, position: i32, resolution: u8) -> List {
-    let reso = match_resolution(resolution);
-    let res = x.into_iter()
-        .map(|(_, robj)| {
-            let child = <&H3>::try_from(&robj)
-                .unwrap()
-                .index
-                .child_at(position as u64, reso);
-            match child {
-                Some(child) => Robj::from(H3::from(child)),
-                None => Robj::from(extendr_api::NULL),
-            }
+fn h3_ancestors_wide_(x: List, min_res: u8) -> List {
+    let cells = cell_indexes(&x);
+
+    let res = (min_res..=15)
+        .map(|res| {
+            let reso = Resolution::try_from(res).unwrap();
+            as_h3_vctr(cells.iter().map(|cell| cell.and_then(|cell| cell.parent(reso))))
         })
-        .collect::<Vec<Robj>>();
+        .collect::<Vec<List>>();
 
     List::from_values(res)
-        .set_class(vctrs_class())
-        .unwrap()
-        .clone()
 }
 
+// is each cell of x a strict descendant of the paired cell of y
 #[extendr]
-fn compact_cells_(x: List) -> List {
-    let h3_vec = x
+fn h3_is_descendant_(x: List, y: List) -> Logicals {
+    cell_indexes(&x)
         .into_iter()
-        .map(|(_, robj)| <&H3>::try_from(&robj).unwrap().index)
-        .collect::<Vec<CellIndex>>();
+        .zip(cell_indexes(&y))
+        .map(|(x, y)| match (x, y) {
+            (Some(x), Some(y)) => {
+                Rbool::from(x.resolution() > y.resolution() && x.parent(y.resolution()) == Some(y))
+            }
+            _ => Rbool::na(),
+        })
+        .collect::<Logicals>()
+}
 
-    let res = CellIndex::compact(h3_vec)
-        .unwrap()
-        .into_iter()
-        .map(|x| H3::from(x))
-        .collect::<Vec<H3>>();
+// the aggregate of the values mapped to a cell at the target resolution
+struct Harmonized {
+    cell: CellIndex,
+    value: f64,
+    n: i32,
+    missing: bool,
+}
 
-    List::from_values(res)
-        .set_class(vctrs_class())
-        .unwrap()
-        .clone()
+// map every cell to `resolution`
+//
+// finer cells are replaced by their parent and coarser cells by their children.
+// When `split` the value of a coarser cell is divided equally between its
+// children, otherwise it is replicated. Returns the position of the source
+// cell, the target cell, and its value. Missing cells are dropped.
+fn harmonize_rows(
+    cells: Vec<Option<CellIndex>>,
+    values: &Doubles,
+    reso: Resolution,
+    split: bool,
+) -> Vec<(usize, CellIndex, Rfloat)> {
+    let mut rows = Vec::new();
+
+    for (i, (cell, value)) in cells.into_iter().zip(values.iter()).enumerate() {
+        let cell = match cell {
+            Some(cell) => cell,
+            None => continue,
+        };
+
+        if cell.resolution() >= reso {
+            // parent() always succeeds at the same or a coarser resolution
+            rows.push((i, cell.parent(reso).unwrap(), value));
+        } else {
+            let value = if split && !value.is_na() {
+                Rfloat::from(value.inner() / cell.children_count(reso) as f64)
+            } else {
+                value
+            };
+
+            rows.extend(cell.children(reso).map(|child| (i, child, value)));
+        }
+    }
+
+    rows
 }
 
+// map every cell to `resolution` and optionally aggregate each target cell
+//
+// Without `aggregate` a row is returned for every source and target cell pair.
+// Otherwise values mapped to the same target cell are combined with `coarsen`
+// which is one of "sum", "mean", "min", or "max".
 #[extendr]
-fn uncompact_cells_(x: List, resolution: u8) -> List {
+fn h3_harmonize_(
+    x: List,
+    values: Doubles,
+    resolution: u8,
+    coarsen: &str,
+    split: bool,
+    aggregate: bool,
+) -> List {
     let reso = match_resolution(resolution);
+    let rows = harmonize_rows(cell_indexes(&x), &values, reso, split);
+
+    if !aggregate {
+        let source = rows
+            .iter()
+            .map(|(i, _, _)| *i as i32 + 1)
+            .collect::<Vec<i32>>();
+        let value = rows.iter().map(|(_, _, value)| *value).collect::<Doubles>();
+
+        return list!(
+            source = source,
+            cell = as_h3_vctr(rows.iter().map(|(_, cell, _)| *cell)),
+            value = value
+        );
+    }
+
+    let mut lookup: HashMap<CellIndex, usize> = HashMap::new();
+    let mut res: Vec<Harmonized> = Vec::new();
+
+    for (_, cell, value) in rows {
+        let i = *lookup.entry(cell).or_insert_with(|| {
+            let init = match coarsen {
+                "min" => f64::INFINITY,
+                "max" => f64::NEG_INFINITY,
+                _ => 0.0,
+            };
+            res.push(Harmonized {
+                cell,
+                value: init,
+                n: 0,
+                missing: false,
+            });
+            res.len() - 1
+        });
+
+        let entry = &mut res[i];
+        entry.n += 1;
+        if value.is_na() {
+            entry.missing = true;
+            continue;
+        }
+
+        let value = value.inner();
+        entry.value = match coarsen {
+            "min" => entry.value.min(value),
+            "max" => entry.value.max(value),
+            _ => entry.value + value,
+        };
+    }
 
-    let res = x.into_iter()
-        .map(|(_, robj)| {
-            if robj.is_null() {
-                list!()
-                    .set_class(vctrs_class())
-                    .unwrap()
-                    .clone()
+    let mean = coarsen == "mean";
+    let value = res
+        .iter()
+        .map(|entry| {
+            if entry.missing {
+                Rfloat::na()
+            } else if mean {
+                Rfloat::from(entry.value / entry.n as f64)
             } else {
-                let uncompacted = CellIndex::uncompact(
-                    std::iter::once(<&H3>::try_from(&robj).unwrap().index),
-                    reso,
-                );
-                let res = uncompacted
-                    .map(|x| H3::from(x))
-                    .collect::<Vec<H3>>();
-
-                List::from_values(res)
-                    .set_class(vctrs_class())
-                    .unwrap()
-                    .clone()
-            } 
+                Rfloat::from(entry.value)
+            }
         })
-        .collect::<Vec<List>>();
+        .collect::<Doubles>();
 
-    List::from_values(res)
-}
+    let n = res.iter().map(|entry| entry.n).collect::<Vec<i32>>();
 
-// skipping uncompactCellSize
+    list!(
+        cell = as_h3_vctr(res.iter().map(|entry| entry.cell)),
+        value = value,
+        n = n
+    )
+}
 
 extendr_module! {
     mod hierarchical;
@@ -188,4 +505,11 @@ extendr_module! {
     fn get_children_at_;
     fn compact_cells_;
     fn uncompact_cells_;
+    fn uncompact_size_;
+    fn uncompact_cells_flat_;
+    fn h3_children_slice_;
+    fn h3_ancestors_long_;
+    fn h3_ancestors_wide_;
+    fn h3_is_descendant_;
+    fn h3_harmonize_;
 }
@@ -1,8 +1,12 @@
 use std::str::FromStr;
 
+use crate::createh3::parse_index;
+use crate::edgefns::{edge_vctrs, H3DEdge};
 use crate::h3::*;
+use crate::int64::{missing_indexes, raw_indexes};
+use crate::vertex::{vertex_vctrs, H3Vertex};
 use extendr_api::prelude::*;
-use h3o::CellIndex;
+use h3o::{BaseCell, CellIndex, DirectedEdgeIndex, Resolution, VertexIndex};
 
 #[extendr]
 /// H3 Inspection Functions
@@ -131,7 +135,6 @@ fn is_pentagon(x: List) -> Logicals {
         .collect::<Logicals>()
 }
 
-// skip CellIndex::icosahedron_faces
 #[extendr]
 /// @export
 /// @rdname inspection
@@ -148,6 +151,186 @@ fn get_face_count(x: List) -> Integers {
         .collect::<Integers>()
 }
 
+// the icosahedron faces intersected by each cell
+#[extendr]
+fn h3_faces_(x: List) -> List {
+    let res = cell_indexes(&x)
+        .into_iter()
+        .map(|cell| match cell {
+            Some(cell) => cell
+                .icosahedron_faces()
+                .iter()
+                .map(|face| Rint::from(u8::from(face) as i32))
+                .collect::<Integers>(),
+            None => Integers::new(0),
+        })
+        .collect::<Vec<Integers>>();
+
+    List::from_values(res)
+}
+
+// the direction digit of each cell at resolutions 1 through 15
+// digits beyond the cell's resolution are NA
+#[extendr]
+fn h3_digits_(x: List) -> RMatrix<i32> {
+    let cells = cell_indexes(&x);
+
+    RMatrix::new_matrix(cells.len(), 15, |r, c| {
+        let res = Resolution::try_from(c as u8 + 1).unwrap();
+        cells[r]
+            .and_then(|cell| cell.direction_at(res))
+            .map_or(i32::MIN, |digit| u8::from(digit) as i32)
+    })
+}
+
+// the reason a 64-bit integer is not a valid cell index
+// checks follow the same order as CellIndex::try_from() except that the mode
+// is checked before the reserved bits which edges and vertexes make use of
+fn validate_index(index: u64) -> &'static str {
+    if index >> 63 != 0 {
+        return "reserved_bits";
+    }
+
+    match (index >> 59) & 0b1111 {
+        1 => (),
+        2 => return "mode_directed_edge",
+        3 => return "mode_undirected_edge",
+        4 => return "mode_vertex",
+        _ => return "invalid_mode",
+    }
+
+    if (index >> 56) & 0b111 != 0 {
+        return "reserved_bits";
+    }
+
+    let base_cell = match BaseCell::try_from(((index >> 45) & 0b111_1111) as u8) {
+        Ok(base_cell) => base_cell,
+        Err(_) => return "invalid_base_cell",
+    };
+
+    let resolution = ((index >> 52) & 0b1111) as u32;
+    let digit = |res: u32| (index >> ((15 - res) * 3)) & 0b111;
+
+    if (resolution + 1..=15).any(|res| digit(res) != 7) {
+        return "invalid_unused_digits";
+    }
+
+    if (1..=resolution).any(|res| digit(res) == 7) {
+        return "invalid_digit";
+    }
+
+    // pentagons have no K axis (1) subsequence
+    if base_cell.is_pentagon() {
+        let first = (1..=resolution).map(digit).find(|d| *d != 0);
+        if first == Some(1) {
+            return "deleted_subsequence";
+        }
+    }
+
+    match CellIndex::try_from(index) {
+        Ok(_) => "valid",
+        Err(_) => "invalid",
+    }
+}
+
+#[extendr]
+fn h3_validate_(x: Strings) -> Strings {
+    x.into_iter()
+        .map(|x| {
+            if x.is_na() {
+                return Rstr::na();
+            }
+
+            match parse_index(x.as_str()) {
+                Some(index) => Rstr::from(validate_index(index)),
+                None => Rstr::from("invalid_string"),
+            }
+        })
+        .collect::<Strings>()
+}
+
+// x contains the bits of 64-bit integers e.g. bit64::integer64
+#[extendr]
+fn h3_validate_bits_(x: Doubles) -> Strings {
+    x.iter()
+        .map(|x| {
+            let bits = x.inner().to_bits();
+            // NA for both doubles and integer64
+            if x.is_na() || bits == 1 << 63 {
+                Rstr::na()
+            } else {
+                Rstr::from(validate_index(bits))
+            }
+        })
+        .collect::<Strings>()
+}
+
+// the type of index given by the mode bits
+// the index must also be a valid index of that type
+fn index_mode(index: u64) -> &'static str {
+    match (index >> 59) & 0b1111 {
+        1 if CellIndex::try_from(index).is_ok() => "cell",
+        2 if DirectedEdgeIndex::try_from(index).is_ok() => "directed_edge",
+        4 if VertexIndex::try_from(index).is_ok() => "vertex",
+        _ => "invalid",
+    }
+}
+
+// x is a character vector or the bits of an integer64 vector
+#[extendr]
+fn h3_index_mode_(x: Robj) -> Strings {
+    let missing = missing_indexes(&x);
+
+    raw_indexes(x)
+        .into_iter()
+        .zip(missing)
+        .map(|(index, missing)| {
+            if missing {
+                return Rstr::na();
+            }
+
+            match index {
+                Some(index) => Rstr::from(index_mode(index)),
+                None => Rstr::from("invalid"),
+            }
+        })
+        .collect::<Strings>()
+}
+
+// split x into a vector for each index type dropping invalid elements
+#[extendr]
+fn h3_parse_any_(x: Robj) -> List {
+    let mut cells = Vec::new();
+    let mut edges = Vec::new();
+    let mut vertexes = Vec::new();
+
+    for index in raw_indexes(x).into_iter().flatten() {
+        match index_mode(index) {
+            "cell" => cells.push(H3::from(CellIndex::try_from(index).unwrap())),
+            "directed_edge" => {
+                edges.push(H3DEdge::from(DirectedEdgeIndex::try_from(index).unwrap()))
+            }
+            "vertex" => vertexes.push(H3Vertex::from(VertexIndex::try_from(index).unwrap())),
+            _ => (),
+        }
+    }
+
+    list!(
+        cell = List::from_values(cells)
+            .set_class(vctrs_class())
+            .unwrap()
+            .clone(),
+        directed_edge = List::from_values(edges)
+            .set_class(edge_vctrs())
+            .unwrap()
+            .clone(),
+        vertex = List::from_values(vertexes)
+            .set_class(vertex_vctrs())
+            .unwrap()
+            .clone()
+    )
+}
+
 extendr_module! {
     mod inspection;
     fn h3_resolution;
@@ -156,4 +339,10 @@ extendr_module! {
     fn is_res_class_iii;
     fn is_pentagon;
     fn get_face_count;
+    fn h3_faces_;
+    fn h3_digits_;
+    fn h3_validate_;
+    fn h3_validate_bits_;
+    fn h3_index_mode_;
+    fn h3_parse_any_;
 }
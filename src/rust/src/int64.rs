@@ -0,0 +1,128 @@
+use extendr_api::prelude::*;
+use h3o::{CellIndex, DirectedEdgeIndex};
+
+use crate::createh3::parse_index;
+use crate::edgefns::{edge_vctrs, H3DEdge};
+use crate::h3::*;
+
+// bit64::integer64 vectors are doubles which hold the bits of an i64
+// the NA value is i64::MIN
+const NA_INTEGER64: u64 = 1 << 63;
+
+fn as_integer64(x: Option<u64>) -> Rfloat {
+    Rfloat::from(f64::from_bits(x.unwrap_or(NA_INTEGER64)))
+}
+
+pub fn from_integer64(x: Rfloat) -> Option<u64> {
+    if x.is_na() {
+        return None;
+    }
+
+    let bits = x.inner().to_bits();
+    if bits == NA_INTEGER64 {
+        None
+    } else {
+        Some(bits)
+    }
+}
+
+// the raw 64-bit indexes of a character vector of IDs or an integer64 vector
+// the type is checked in R so any other vector is treated as all missing
+pub fn raw_indexes(x: Robj) -> Vec<Option<u64>> {
+    if let Ok(x) = Strings::try_from(x.clone()) {
+        x.iter()
+            .map(|xi| {
+                if xi.is_na() {
+                    None
+                } else {
+                    parse_index(xi.as_str())
+                }
+            })
+            .collect::<Vec<Option<u64>>>()
+    } else if let Ok(x) = Doubles::try_from(x.clone()) {
+        x.iter().map(from_integer64).collect::<Vec<Option<u64>>>()
+    } else {
+        vec![None; x.len()]
+    }
+}
+
+// which elements of a character or integer64 vector are missing
+pub fn missing_indexes(x: &Robj) -> Vec<bool> {
+    if let Ok(x) = Strings::try_from(x.clone()) {
+        x.iter().map(|xi| xi.is_na()).collect::<Vec<bool>>()
+    } else if let Ok(x) = Doubles::try_from(x.clone()) {
+        x.iter()
+            .map(|xi| from_integer64(xi).is_none())
+            .collect::<Vec<bool>>()
+    } else {
+        vec![]
+    }
+}
+
+#[extendr]
+fn h3_to_int64_(x: List) -> Doubles {
+    x.iter()
+        .map(|(_, robj)| {
+            let index = <&H3>::try_from(&robj).ok().map(|h3| u64::from(h3.index));
+            as_integer64(index)
+        })
+        .collect::<Doubles>()
+}
+
+#[extendr]
+fn h3_from_int64_(x: Doubles) -> List {
+    let res = x
+        .iter()
+        .map(|xi| {
+            let cell = from_integer64(xi).and_then(|bits| CellIndex::try_from(bits).ok());
+            match cell {
+                Some(cell) => Robj::from(H3::from(cell)),
+                None => Robj::from(extendr_api::NULL),
+            }
+        })
+        .collect::<Vec<Robj>>();
+
+    List::from_values(res)
+        .set_class(vctrs_class())
+        .unwrap()
+        .clone()
+}
+
+#[extendr]
+fn edges_to_int64_(x: List) -> Doubles {
+    x.iter()
+        .map(|(_, robj)| {
+            let index = <&H3DEdge>::try_from(&robj)
+                .ok()
+                .map(|edge| u64::from(edge.edge));
+            as_integer64(index)
+        })
+        .collect::<Doubles>()
+}
+
+#[extendr]
+fn edges_from_int64_(x: Doubles) -> List {
+    let res = x
+        .iter()
+        .map(|xi| {
+            let edge = from_integer64(xi).and_then(|bits| DirectedEdgeIndex::try_from(bits).ok());
+            match edge {
+                Some(edge) => Robj::from(H3DEdge::from(edge)),
+                None => Robj::from(extendr_api::NULL),
+            }
+        })
+        .collect::<Vec<Robj>>();
+
+    List::from_values(res)
+        .set_class(edge_vctrs())
+        .unwrap()
+        .clone()
+}
+
+extendr_module! {
+    mod int64;
+    fn h3_to_int64_;
+    fn h3_from_int64_;
+    fn edges_to_int64_;
+    fn edges_from_int64_;
+}
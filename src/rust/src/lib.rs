@@ -1,13 +1,20 @@
 use extendr_api::prelude::*;
 
+mod coverage;
 mod createh3;
+mod distance;
 mod edgefns;
 mod fromsf;
+mod grid_search;
 mod grid_traversal;
 mod h3;
 mod hierarchical;
 mod inspection;
+mod int64;
+mod sets;
+mod sorting;
 mod togeo;
+mod vertex;
 
 extendr_module! {
     mod h3o;
@@ -19,5 +26,12 @@ extendr_module! {
     use togeo;
     use edgefns;
     use grid_traversal;
+    use grid_search;
+    use distance;
+    use sets;
+    use sorting;
+    use int64;
+    use vertex;
+    use coverage;
 }
 
@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+
+use extendr_api::prelude::*;
+use h3o::CellIndex;
+
+use crate::h3::*;
+
+// find the position of `cell` in `table`
+// when `hierarchical` the closest ancestor of `cell` in `table` is also matched
+fn match_cell(
+    cell: CellIndex,
+    table: &HashMap<CellIndex, usize>,
+    hierarchical: bool,
+) -> Option<usize> {
+    if let Some(i) = table.get(&cell) {
+        return Some(*i);
+    }
+
+    if !hierarchical {
+        return None;
+    }
+
+    ancestors(cell).find_map(|parent| table.get(&parent).copied())
+}
+
+// lookup table of the first position of each cell
+fn position_table(x: &[Option<CellIndex>]) -> HashMap<CellIndex, usize> {
+    let mut table = HashMap::with_capacity(x.len());
+    for (i, cell) in x.iter().enumerate() {
+        if let Some(cell) = cell {
+            table.entry(*cell).or_insert(i);
+        }
+    }
+    table
+}
+
+// remove duplicates keeping the first occurrence
+pub fn unique_cells(x: impl IntoIterator<Item = CellIndex>) -> Vec<CellIndex> {
+    let mut seen = HashSet::new();
+    x.into_iter()
+        .filter(|cell| seen.insert(*cell))
+        .collect::<Vec<CellIndex>>()
+}
+
+// remove cells which are covered by a coarser cell of the same set
+pub fn drop_descendants(x: Vec<CellIndex>) -> Vec<CellIndex> {
+    let table = position_table(&x.iter().copied().map(Some).collect::<Vec<_>>());
+    x.into_iter()
+        .filter(|cell| ancestors(*cell).all(|parent| !table.contains_key(&parent)))
+        .collect::<Vec<CellIndex>>()
+}
+
+#[extendr]
+fn h3_unique_(x: List) -> List {
+    let mut seen = HashSet::new();
+    let mut seen_null = false;
+
+    let res = cell_indexes(&x)
+        .into_iter()
+        .filter(|cell| match cell {
+            Some(cell) => seen.insert(*cell),
+            None => !std::mem::replace(&mut seen_null, true),
+        })
+        .map(|cell| match cell {
+            Some(cell) => Robj::from(H3::from(cell)),
+            None => Robj::from(extendr_api::NULL),
+        })
+        .collect::<Vec<Robj>>();
+
+    List::from_values(res)
+        .set_class(vctrs_class())
+        .unwrap()
+        .clone()
+}
+
+#[extendr]
+fn h3_match_(x: List, table: List, hierarchical: bool) -> Integers {
+    let table = position_table(&cell_indexes(&table));
+
+    cell_indexes(&x)
+        .into_iter()
+        .map(|cell| {
+            let pos = cell.and_then(|cell| match_cell(cell, &table, hierarchical));
+            match pos {
+                Some(pos) => Rint::from(pos as i32 + 1),
+                None => Rint::na(),
+            }
+        })
+        .collect::<Integers>()
+}
+
+#[extendr]
+fn h3_union_(x: List, y: List, hierarchical: bool) -> List {
+    let cells = cell_indexes(&x)
+        .into_iter()
+        .chain(cell_indexes(&y))
+        .flatten();
+
+    let mut res = unique_cells(cells);
+    if hierarchical {
+        res = drop_descendants(res);
+    }
+
+    as_h3_vctr(res)
+}
+
+#[extendr]
+fn h3_intersect_(x: List, y: List, hierarchical: bool) -> List {
+    let x = cell_indexes(&x);
+    let y = cell_indexes(&y);
+    let x_table = position_table(&x);
+    let y_table = position_table(&y);
+
+    let in_y = x
+        .into_iter()
+        .flatten()
+        .filter(|cell| match_cell(*cell, &y_table, hierarchical).is_some());
+
+    let res = if hierarchical {
+        // cells of y which are covered by a coarser cell of x
+        let in_x = y
+            .into_iter()
+            .flatten()
+            .filter(|cell| match_cell(*cell, &x_table, hierarchical).is_some());
+        drop_descendants(unique_cells(in_y.chain(in_x)))
+    } else {
+        unique_cells(in_y)
+    };
+
+    as_h3_vctr(res)
+}
+
+#[extendr]
+fn h3_setdiff_(x: List, y: List, hierarchical: bool) -> List {
+    let y_table = position_table(&cell_indexes(&y));
+
+    let res = cell_indexes(&x)
+        .into_iter()
+        .flatten()
+        .filter(|cell| match_cell(*cell, &y_table, hierarchical).is_none());
+
+    as_h3_vctr(unique_cells(res))
+}
+
+extendr_module! {
+    mod sets;
+    fn h3_unique_;
+    fn h3_match_;
+    fn h3_union_;
+    fn h3_intersect_;
+    fn h3_setdiff_;
+}
@@ -0,0 +1,74 @@
+use extendr_api::prelude::*;
+use h3o::{CellIndex, LatLng};
+
+use crate::h3::*;
+
+// the cell index with the resolution bits cleared
+// descendants sort next to their ancestors because the unused digits of a
+// coarser cell are all set, placing it directly after its children
+fn index_key(cell: CellIndex) -> u64 {
+    u64::from(cell) & !(0b1111 << 52)
+}
+
+// position of (x, y) along a Hilbert curve filling a 2^order square
+fn hilbert_key(mut x: u64, mut y: u64, order: u32) -> u64 {
+    let n = 1_u64 << order;
+    let mut d = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
+
+// Hilbert key of the cell center using longitude and latitude as the plane
+fn center_key(cell: CellIndex) -> u64 {
+    // 32 bits per axis keeps cells at the finest resolutions apart
+    const ORDER: u32 = 32;
+    let scale = ((1_u64 << ORDER) - 1) as f64;
+    let ll = LatLng::from(cell);
+    let x = ((ll.lng() + 180.0) / 360.0 * scale).round() as u64;
+    let y = ((ll.lat() + 90.0) / 180.0 * scale).round() as u64;
+    hilbert_key(x, y, ORDER)
+}
+
+#[extendr]
+fn h3_order_(x: List, method: &str) -> Integers {
+    let key: fn(CellIndex) -> u64 = match method {
+        "hilbert" => center_key,
+        _ => index_key,
+    };
+
+    let mut keys = cell_indexes(&x)
+        .into_iter()
+        .enumerate()
+        .map(|(i, cell)| (cell.map(key), i))
+        .collect::<Vec<(Option<u64>, usize)>>();
+
+    // missing cells are placed last
+    keys.sort_by_key(|(key, i)| (key.is_none(), *key, *i));
+
+    keys.into_iter()
+        .map(|(_, i)| Rint::from(i as i32 + 1))
+        .collect::<Integers>()
+}
+
+extendr_module! {
+    mod sorting;
+    fn h3_order_;
+}
@@ -0,0 +1,43 @@
+use extendr_api::prelude::*;
+use h3o::VertexIndex;
+
+// H3Vertex implementation. Defines a struct to be used for cell vertexes
+// a list of H3Vertexes is a H3Vertex vector.
+#[derive(Debug, Clone, Copy)]
+pub struct H3Vertex {
+    pub vertex: VertexIndex,
+}
+
+#[extendr]
+impl H3Vertex {}
+
+impl From<VertexIndex> for H3Vertex {
+    fn from(value: VertexIndex) -> Self {
+        H3Vertex { vertex: value }
+    }
+}
+
+#[extendr]
+pub fn vertex_vctrs() -> [String; 3] {
+    [
+        String::from("H3Vertex"),
+        String::from("vctrs_vctr"),
+        String::from("list"),
+    ]
+}
+
+#[extendr]
+fn vertexes_to_strings(x: List) -> Strings {
+    x.iter()
+        .map(|(_, robj)| match <&H3Vertex>::try_from(&robj) {
+            Ok(vertex) => Rstr::from_string(&vertex.vertex.to_string()),
+            Err(_) => Rstr::na(),
+        })
+        .collect::<Strings>()
+}
+
+extendr_module! {
+    mod vertex;
+    fn vertex_vctrs;
+    fn vertexes_to_strings;
+}